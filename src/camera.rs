@@ -0,0 +1,102 @@
+//! Camera setup, including the HDR + bloom + tonemapping post-processing
+//! pipeline that makes the bullet tracers and point light actually pop, and
+//! the chase camera that keeps the local player framed as they move.
+//! Kept in this one module so all of that config lives in one place instead
+//! of inline in `setup`; `CameraPlugin` only registers the chase system, and
+//! the post-processing has no on/off switch.
+
+use bevy::core_pipeline::bloom::{BloomCompositeMode, BloomSettings};
+use bevy::core_pipeline::tonemapping::{DebandDither, Tonemapping};
+use bevy::prelude::*;
+
+use crate::PlayerSlot;
+
+/// Registers `update_camera` - post-processing config itself still lives in
+/// `spawn_camera`, called directly from `setup`, since the camera entity
+/// needs to exist before anything could meaningfully hook into it.
+pub struct CameraPlugin;
+
+impl Plugin for CameraPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, update_camera);
+    }
+}
+
+/// How the camera should sit relative to (and look past) the player it's
+/// tracking. Swap this component's values to tune a tight vs. laggy follow
+/// feel from one place instead of magic numbers in `setup`.
+#[derive(Component, Clone, Copy)]
+pub struct CameraTrackingOffset {
+    pub offset: Vec3,
+    /// higher = camera catches up to the player faster (tighter follow)
+    pub smoothing: f32,
+    /// how far past the player's position the camera looks, down the road
+    pub look_ahead: f32,
+}
+
+impl Default for CameraTrackingOffset {
+    fn default() -> Self {
+        Self {
+            offset: Vec3::new(0.0, 2.0, 6.0),
+            smoothing: 8.0,
+            look_ahead: 2.0,
+        }
+    }
+}
+
+/// Spawns the main camera with HDR enabled, low-intensity additive bloom,
+/// and filmic tonemapping, so materials pushed above 1.0 (see the player
+/// bullet material in `main.rs` and the enemy/enemy bullet materials in
+/// `enemy.rs`) actually glow instead of clipping.
+/// Also attaches `CameraTrackingOffset` so `update_camera` keeps it chasing
+/// the local player.
+pub fn spawn_camera(commands: &mut Commands, transform: Transform) {
+    commands.spawn((
+        Camera3dBundle {
+            transform,
+            camera: Camera {
+                hdr: true,
+                ..default()
+            },
+            tonemapping: Tonemapping::BlenderFilmic,
+            deband_dither: DebandDither::Enabled,
+            ..default()
+        },
+        BloomSettings {
+            intensity: 0.04,
+            composite_mode: BloomCompositeMode::Additive,
+            ..default()
+        },
+        CameraTrackingOffset::default(),
+    ));
+}
+
+/// Moves the camera toward `local_player.translation + offset` with
+/// exponential smoothing (so the follow feel doesn't depend on frame rate)
+/// and keeps it looking at a point `look_ahead` down the road from the
+/// player. This is purely cosmetic and runs in `Update` off real time, not
+/// `Simulate`/`Res<SimFrame>` - a camera doesn't need to be rewound.
+pub fn update_camera(
+    time: Res<Time>,
+    mut camera: Query<(&mut Transform, &CameraTrackingOffset), Without<PlayerSlot>>,
+    players: Query<(&Transform, &PlayerSlot)>,
+) {
+    let Some(player) = players
+        .iter()
+        .find(|(_, slot)| **slot == PlayerSlot::Local)
+        .map(|(t, _)| t)
+    else {
+        return;
+    };
+
+    for (mut cam_t, tracking) in &mut camera {
+        let desired = player.translation + tracking.offset;
+        let lerp_factor = 1.0 - (-tracking.smoothing * time.delta_seconds()).exp();
+        cam_t.translation = cam_t.translation.lerp(desired, lerp_factor);
+
+        let look_at = player.translation - Vec3::new(0.0, 0.0, tracking.look_ahead);
+        cam_t.rotation = Transform::from_translation(cam_t.translation)
+            .looking_at(look_at, Vec3::Y)
+            .rotation;
+    }
+}
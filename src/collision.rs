@@ -0,0 +1,213 @@
+//! Generic AABB collision detection.
+//!
+//! Entities that want to participate attach a `CollisionBox` (half-extents
+//! in world space) plus a `CollisionLayer`/`CollisionMask` pair. Each
+//! `Simulate` tick, `detect_collisions` builds an AABB per entity from
+//! `Transform + CollisionBox` (not `GlobalTransform`: that's only synced by
+//! Bevy's `PostUpdate` propagation once per rendered frame, which lags
+//! behind however many `Simulate` ticks a rollback resimulation runs in a
+//! single frame; every entity here is unparented anyway, so plain
+//! `Transform` is both correct and current), does broad-phase by sorting
+//! along the road's z-axis and only comparing overlapping spans, and emits
+//! a `CollisionBegin` event for any overlapping pair whose layer/mask
+//! match. Gameplay systems react to the events instead of embedding
+//! geometry math (see `main::on_bullet_enemy_collision`).
+
+use bevy::prelude::*;
+
+/// Half-extents of the entity's bounding box in world space.
+#[derive(Component, Clone, Copy)]
+pub struct CollisionBox {
+    pub half_extents: Vec3,
+}
+
+/// This frame's displacement, used to expand a fast mover's AABB along its
+/// travel so it can't tunnel through something thin (e.g. a bullet at
+/// `BULLET_VEL` skipping over a 0.3m-wide enemy between ticks).
+#[derive(Component, Clone, Copy, Default)]
+pub struct Sweep(pub Vec3);
+
+/// Which layer(s) this entity belongs to.
+#[derive(Component, Clone, Copy)]
+pub struct CollisionLayer(pub u32);
+
+/// Which layer(s) this entity tests against.
+#[derive(Component, Clone, Copy)]
+pub struct CollisionMask(pub u32);
+
+impl CollisionLayer {
+    pub const PLAYER: Self = Self(1 << 0);
+    pub const ENEMY: Self = Self(1 << 1);
+    pub const BULLET: Self = Self(1 << 2);
+}
+
+impl CollisionMask {
+    pub const NONE: Self = Self(0);
+    pub const PLAYER: Self = Self(CollisionLayer::PLAYER.0);
+    pub const ENEMY: Self = Self(CollisionLayer::ENEMY.0);
+    pub const BULLET: Self = Self(CollisionLayer::BULLET.0);
+}
+
+/// Fired for every overlapping pair whose layer/mask match this tick.
+/// `a`/`b` order matches whatever order the broad-phase happened to sort
+/// them in, so readers should check both ways around.
+#[derive(Event, Clone, Copy)]
+pub struct CollisionBegin {
+    pub a: Entity,
+    pub b: Entity,
+}
+
+struct Aabb {
+    min: Vec3,
+    max: Vec3,
+}
+
+impl Aabb {
+    fn overlaps(&self, other: &Aabb) -> bool {
+        self.min.x <= other.max.x
+            && self.max.x >= other.min.x
+            && self.min.y <= other.max.y
+            && self.max.y >= other.min.y
+            && self.min.z <= other.max.z
+            && self.max.z >= other.min.z
+    }
+}
+
+pub fn detect_collisions(
+    query: Query<(
+        Entity,
+        &Transform,
+        &CollisionBox,
+        &CollisionLayer,
+        &CollisionMask,
+        Option<&Sweep>,
+    )>,
+    mut events: EventWriter<CollisionBegin>,
+) {
+    let mut entries: Vec<(Entity, Aabb, u32, u32)> = query
+        .iter()
+        .map(|(e, t, cbox, layer, mask, sweep)| {
+            let center = t.translation;
+            let displacement = sweep.copied().unwrap_or_default().0;
+            let mut min = center - cbox.half_extents;
+            let mut max = center + cbox.half_extents;
+            // `center` is already this tick's end position, so the swept
+            // span reaches back to where the mover started the tick
+            min = min.min(center - displacement - cbox.half_extents);
+            max = max.max(center - displacement + cbox.half_extents);
+            (e, Aabb { min, max }, layer.0, mask.0)
+        })
+        .collect();
+
+    // broad-phase: sort along the road's z-axis, only compare entries whose
+    // z spans actually overlap instead of the full n^2 pair set
+    entries.sort_by(|a, b| a.1.min.z.partial_cmp(&b.1.min.z).unwrap());
+
+    for i in 0..entries.len() {
+        for j in (i + 1)..entries.len() {
+            if entries[j].1.min.z > entries[i].1.max.z {
+                // sorted by z.min, so nothing further along can overlap i either
+                break;
+            }
+            let (e_a, aabb_a, layer_a, mask_a) = &entries[i];
+            let (e_b, aabb_b, layer_b, mask_b) = &entries[j];
+            let layers_match = (layer_a & mask_b) != 0 || (layer_b & mask_a) != 0;
+            if layers_match && aabb_a.overlaps(aabb_b) {
+                events.send(CollisionBegin { a: *e_a, b: *e_b });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::ecs::event::Events;
+    use bevy::ecs::system::RunSystemOnce;
+
+    #[test]
+    fn aabb_overlap_cases() {
+        let a = Aabb {
+            min: Vec3::splat(-1.0),
+            max: Vec3::splat(1.0),
+        };
+        let overlapping = Aabb {
+            min: Vec3::splat(0.5),
+            max: Vec3::splat(2.0),
+        };
+        assert!(a.overlaps(&overlapping));
+
+        // touching exactly at the boundary counts as overlapping (<=/>=)
+        let touching = Aabb {
+            min: Vec3::splat(1.0),
+            max: Vec3::splat(2.0),
+        };
+        assert!(a.overlaps(&touching));
+
+        let disjoint = Aabb {
+            min: Vec3::splat(5.0),
+            max: Vec3::splat(6.0),
+        };
+        assert!(!a.overlaps(&disjoint));
+    }
+
+    #[test]
+    fn detect_collisions_respects_layer_mask_and_broad_phase() {
+        let mut world = World::new();
+        world.insert_resource(Events::<CollisionBegin>::default());
+
+        // overlapping pair whose layer/mask actually match each other
+        let bullet = world
+            .spawn((
+                Transform::from_xyz(0.0, 0.0, 0.0),
+                CollisionBox {
+                    half_extents: Vec3::splat(0.5),
+                },
+                CollisionLayer::BULLET,
+                CollisionMask::ENEMY,
+            ))
+            .id();
+        let enemy = world
+            .spawn((
+                Transform::from_xyz(0.2, 0.0, 0.0),
+                CollisionBox {
+                    half_extents: Vec3::splat(0.5),
+                },
+                CollisionLayer::ENEMY,
+                CollisionMask::BULLET,
+            ))
+            .id();
+
+        // overlapping the bullet/enemy above, but its mask doesn't include
+        // either of their layers (nor theirs its), so the layer/mask check
+        // should reject the pair even though the broad-phase compares it
+        world.spawn((
+            Transform::from_xyz(0.0, 0.0, 0.0),
+            CollisionBox {
+                half_extents: Vec3::splat(0.5),
+            },
+            CollisionLayer::PLAYER,
+            CollisionMask::NONE,
+        ));
+
+        // far enough along z that the sorted broad-phase should never even
+        // compare it against the pair above
+        world.spawn((
+            Transform::from_xyz(0.0, 0.0, 100.0),
+            CollisionBox {
+                half_extents: Vec3::splat(0.5),
+            },
+            CollisionLayer::BULLET,
+            CollisionMask::ENEMY,
+        ));
+
+        world.run_system_once(detect_collisions);
+
+        let events = world.resource::<Events<CollisionBegin>>();
+        let mut reader = events.get_reader();
+        let seen: Vec<(Entity, Entity)> = reader.read(events).map(|e| (e.a, e.b)).collect();
+
+        assert_eq!(seen.len(), 1);
+        assert!(seen[0] == (bullet, enemy) || seen[0] == (enemy, bullet));
+    }
+}
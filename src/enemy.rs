@@ -0,0 +1,197 @@
+//! Enemy AI: patrol a bounded rectangle when no player is in range, and
+//! track + strafe + shoot once one wanders in.
+
+use bevy::prelude::*;
+use rand::Rng;
+use std::ops::RangeInclusive;
+
+use crate::collision::{CollisionBox, CollisionLayer, CollisionMask, Sweep};
+use crate::net::{SimFrame, SimRng, FIXED_DT, FIXED_HZ};
+use crate::{Gun, Player, BULLET_VEL, ENEMY_GUN_PERIOD, ENEMY_SIZE, ENEMY_VEL};
+
+#[derive(Component)]
+pub struct Enemy;
+
+/// Marks a bullet fired by `enemy_shoot`, so the collision reaction knows
+/// it came from the enemy side and should test against the player instead
+/// of the other way round.
+#[derive(Component)]
+pub struct EnemyBullet;
+
+/// The rectangle (in world x/z) an enemy wanders inside while no player is
+/// near, and the range it'll engage a player within.
+#[derive(Component, Clone)]
+pub struct PatrolBounds {
+    pub x: RangeInclusive<f32>,
+    pub z: RangeInclusive<f32>,
+}
+
+impl PatrolBounds {
+    fn contains(&self, pos: Vec3) -> bool {
+        self.x.contains(&pos.x) && self.z.contains(&pos.z)
+    }
+
+    /// Picks a new wander point from the deterministic `SimRng`, not
+    /// `rand::thread_rng()` - an OS-seeded RNG called from inside `Simulate`
+    /// would make the two rollback peers' enemies diverge the instant a
+    /// patrol leg finished.
+    fn random_point(&self, rng: &mut SimRng) -> Vec3 {
+        Vec3::new(
+            rng.0.gen_range(self.x.clone()),
+            0.0,
+            rng.0.gen_range(self.z.clone()),
+        )
+    }
+}
+
+/// Where the enemy is currently walking to while patrolling. `None` means
+/// "pick a new one", which also covers the first tick after spawn.
+#[derive(Component, Clone, Copy, Default)]
+pub struct PatrolTarget(pub Option<Vec3>);
+
+#[derive(Component, Clone, Copy, PartialEq, Eq, Default)]
+pub enum EnemyState {
+    #[default]
+    Patrol,
+    Engage,
+}
+
+const PATROL_ARRIVE_DIST: f32 = 0.1;
+
+pub fn move_enemies(
+    mut enemies: Query<
+        (&mut Transform, &PatrolBounds, &mut PatrolTarget, &mut EnemyState),
+        With<Enemy>,
+    >,
+    players: Query<&Transform, (With<Player>, Without<Enemy>)>,
+    mut rng: ResMut<SimRng>,
+) {
+    for (mut t, bounds, mut target, mut state) in &mut enemies {
+        let nearest_player = players.iter().min_by(|a, b| {
+            let da = (a.translation - t.translation).length_squared();
+            let db = (b.translation - t.translation).length_squared();
+            da.partial_cmp(&db).unwrap()
+        });
+
+        let in_range = nearest_player.is_some_and(|p| bounds.contains(p.translation));
+        *state = if in_range {
+            EnemyState::Engage
+        } else {
+            EnemyState::Patrol
+        };
+
+        match (*state, nearest_player) {
+            (EnemyState::Engage, Some(player)) => {
+                let dx = player.translation.x - t.translation.x;
+                t.translation.x += dx.clamp(-1.0, 1.0) * ENEMY_VEL * FIXED_DT;
+                let dz = (player.translation.z - t.translation.z).signum();
+                t.translation.z += dz * ENEMY_VEL * FIXED_DT;
+            }
+            _ => {
+                let arrived = target
+                    .0
+                    .is_none_or(|tgt| t.translation.distance(tgt) < PATROL_ARRIVE_DIST);
+                if arrived {
+                    target.0 = Some(bounds.random_point(&mut rng));
+                }
+                if let Some(tgt) = target.0 {
+                    let to_target = tgt - t.translation;
+                    if to_target.length_squared() > f32::EPSILON {
+                        t.translation += to_target.normalize() * ENEMY_VEL * FIXED_DT;
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Mirrors `shoot`, but only fires while the enemy is actively engaging a
+/// player, and tags the bullet as `EnemyBullet` so it collides with the
+/// player instead of other enemies.
+pub fn enemy_shoot(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    frame: Res<SimFrame>,
+    mut guns: Query<(&Transform, &mut Gun, &EnemyState), With<Enemy>>,
+) {
+    let period_frames = (ENEMY_GUN_PERIOD * FIXED_HZ as f32) as u32;
+    for (pos, mut gun, state) in &mut guns {
+        if *state != EnemyState::Engage {
+            continue;
+        }
+        if frame.0 - gun.last_fired > period_frames {
+            gun.last_fired = frame.0;
+        } else {
+            continue;
+        }
+
+        let mesh = meshes.add(Sphere::new(0.05));
+        // pushed above 1.0 so it blooms, same trick as the player's tracer
+        let material = materials.add(StandardMaterial {
+            base_color: Color::rgb(6.0, 0.3, 0.3),
+            unlit: true,
+            ..default()
+        });
+
+        commands
+            .spawn(PbrBundle {
+                transform: Transform::default().with_translation(pos.translation),
+                mesh,
+                material,
+                ..default()
+            })
+            .insert(EnemyBullet)
+            .insert(Sweep::default())
+            .insert(CollisionBox {
+                half_extents: Vec3::splat(0.05),
+            })
+            .insert(CollisionLayer::BULLET)
+            .insert(CollisionMask::PLAYER);
+    }
+}
+
+/// Mirrors `move_bullet`, but travels toward the player (+z, back up the
+/// road) instead of away from them, since an `EnemyBullet` starts out near
+/// an enemy at negative z.
+pub fn move_enemy_bullet(
+    mut commands: Commands,
+    mut bullet_pos: Query<(&mut Transform, &mut Sweep, Entity), With<EnemyBullet>>,
+) {
+    for (mut p, mut sweep, e) in &mut bullet_pos {
+        if p.translation.z.abs() > 100. {
+            commands.entity(e).despawn();
+            continue;
+        }
+        let displacement = Vec3::new(0.0, 0.0, FIXED_DT * BULLET_VEL);
+        sweep.0 = displacement;
+        p.translation += displacement;
+    }
+}
+
+pub fn spawn(commands: &mut Commands, meshes: &mut Assets<Mesh>, materials: &mut Assets<StandardMaterial>) {
+    commands
+        .spawn(PbrBundle {
+            mesh: meshes.add(Cuboid::new(ENEMY_SIZE, ENEMY_SIZE, ENEMY_SIZE)),
+            material: materials.add(StandardMaterial {
+                base_color: Color::RED,
+                emissive: Color::rgb(2.0, 0.0, 0.0),
+                ..default()
+            }),
+            transform: Transform::from_xyz(0.0, ENEMY_SIZE / 2.0, -5.0),
+            ..default()
+        })
+        .insert(Enemy)
+        .insert(Gun::default())
+        .insert(PatrolBounds {
+            x: -1.8..=1.8,
+            z: -8.0..=-2.0,
+        })
+        .insert(PatrolTarget::default())
+        .insert(EnemyState::default())
+        .insert(CollisionBox {
+            half_extents: Vec3::splat(ENEMY_SIZE / 2.0),
+        })
+        .insert(CollisionLayer::ENEMY)
+        .insert(CollisionMask::PLAYER);
+}
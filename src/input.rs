@@ -0,0 +1,132 @@
+//! Input abstraction: logical actions bound to keyboard keys and gamepad
+//! buttons/axes, resolved once per tick into an `ActionState` that the rest
+//! of the game reads instead of touching `ButtonInput`/`Gamepad` directly.
+//! This also happens to be exactly what rollback netcode needs: a single
+//! per-frame value that's cheap to serialize (`net::PackedInput::from_action_state`
+//! packs it up for the wire), instead of scattered device queries.
+
+use bevy::prelude::*;
+use std::collections::HashMap;
+
+/// A deadzone below which stick tilt is treated as centered, so a
+/// slightly-off-center stick doesn't drift the player.
+const STICK_DEADZONE: f32 = 0.15;
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Action {
+    MoveLeft,
+    MoveRight,
+    MoveForward,
+    MoveBack,
+    Fire,
+}
+
+/// Maps each logical action to the keys and gamepad buttons that trigger
+/// it. Swap this resource out (or mutate it) to remap controls.
+#[derive(Resource)]
+pub struct Bindings {
+    keys: HashMap<Action, Vec<KeyCode>>,
+    buttons: HashMap<Action, Vec<GamepadButtonType>>,
+}
+
+impl Default for Bindings {
+    fn default() -> Self {
+        use Action::*;
+        let keys = HashMap::from([
+            (MoveLeft, vec![KeyCode::ArrowLeft]),
+            (MoveRight, vec![KeyCode::ArrowRight]),
+            (MoveForward, vec![KeyCode::ArrowUp]),
+            (MoveBack, vec![KeyCode::ArrowDown]),
+            (Fire, vec![KeyCode::Space]),
+        ]);
+        let buttons = HashMap::from([
+            (MoveLeft, vec![GamepadButtonType::DPadLeft]),
+            (MoveRight, vec![GamepadButtonType::DPadRight]),
+            (MoveForward, vec![GamepadButtonType::DPadUp]),
+            (MoveBack, vec![GamepadButtonType::DPadDown]),
+            (Fire, vec![GamepadButtonType::South]),
+        ]);
+        Self { keys, buttons }
+    }
+}
+
+impl Bindings {
+    fn pressed(
+        &self,
+        action: Action,
+        keyboard: &ButtonInput<KeyCode>,
+        gamepads: &Gamepads,
+        gamepad_buttons: &ButtonInput<GamepadButton>,
+    ) -> bool {
+        let via_key = self
+            .keys
+            .get(&action)
+            .is_some_and(|keys| keys.iter().any(|k| keyboard.pressed(*k)));
+        let via_pad = self.buttons.get(&action).is_some_and(|buttons| {
+            gamepads.iter().any(|pad| {
+                buttons
+                    .iter()
+                    .any(|b| gamepad_buttons.pressed(GamepadButton::new(pad, *b)))
+            })
+        });
+        via_key || via_pad
+    }
+}
+
+/// The resolved input for this tick: a continuous movement axis (keyboard
+/// presses show up as -1/0/1 per component, scaled down to unit length on a
+/// diagonal; a stick gives the analog value in between) plus whether fire is
+/// held.
+#[derive(Resource, Default, Clone, Copy)]
+pub struct ActionState {
+    pub move_axis: Vec2,
+    pub fire: bool,
+}
+
+/// Reads keyboard + gamepad state and resolves it into `ActionState`. This
+/// is the only system allowed to touch raw input resources directly;
+/// everything downstream (`move_player`, `shoot`, the rollback transport)
+/// reads `ActionState`/`net::PackedInput` instead.
+pub fn gather_input(
+    keyboard: Res<ButtonInput<KeyCode>>,
+    gamepads: Res<Gamepads>,
+    gamepad_buttons: Res<ButtonInput<GamepadButton>>,
+    gamepad_axes: Res<Axis<GamepadAxis>>,
+    bindings: Res<Bindings>,
+    mut state: ResMut<ActionState>,
+) {
+    let mut axis = Vec2::ZERO;
+    if bindings.pressed(Action::MoveLeft, &keyboard, &gamepads, &gamepad_buttons) {
+        axis.x -= 1.0;
+    }
+    if bindings.pressed(Action::MoveRight, &keyboard, &gamepads, &gamepad_buttons) {
+        axis.x += 1.0;
+    }
+    if bindings.pressed(Action::MoveForward, &keyboard, &gamepads, &gamepad_buttons) {
+        axis.y -= 1.0;
+    }
+    if bindings.pressed(Action::MoveBack, &keyboard, &gamepads, &gamepad_buttons) {
+        axis.y += 1.0;
+    }
+
+    // an analog stick past the deadzone overrides the digital reading on
+    // whichever component it's actually tilted on
+    for pad in gamepads.iter() {
+        let stick_x = gamepad_axes
+            .get(GamepadAxis::new(pad, GamepadAxisType::LeftStickX))
+            .unwrap_or(0.0);
+        let stick_y = gamepad_axes
+            .get(GamepadAxis::new(pad, GamepadAxisType::LeftStickY))
+            .unwrap_or(0.0);
+        if stick_x.abs() > STICK_DEADZONE {
+            axis.x = stick_x;
+        }
+        if stick_y.abs() > STICK_DEADZONE {
+            axis.y = -stick_y; // stick forward (+y) should move the player forward (-z)
+        }
+    }
+    axis = axis.clamp_length_max(1.0);
+
+    state.fire = bindings.pressed(Action::Fire, &keyboard, &gamepads, &gamepad_buttons);
+    state.move_axis = axis;
+}
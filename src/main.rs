@@ -5,17 +5,36 @@ use bevy::window::WindowResolution;
 use bevy_inspector_egui::quick::WorldInspectorPlugin;
 use std::f32::consts::PI;
 
+mod camera;
+mod collision;
+mod enemy;
+mod input;
+mod net;
+
+use collision::{CollisionBegin, CollisionBox, CollisionLayer, CollisionMask, Sweep};
+use enemy::{Enemy, EnemyBullet};
+use input::{gather_input, ActionState, Bindings};
+use net::{
+    rollback_driver, FrameInputs, NetConfig, PackedInput, RollbackHistory, SimFrame, SimRng,
+    Simulate, Transport, FIXED_HZ,
+};
+
 // length of each road segment
 static LEN_SEG: f32 = 1.8;
 // how fast the road moves forward, m/s
 static ROAD_VEL: f32 = 1.0;
 static PLAYER_VEL: f32 = 1.0;
 static PLAYER_GUN_PERIOD: f32 = 0.5;
-static BULLET_VEL: f32 = 50.0;
-static ENEMY_SIZE: f32 = 0.3;
-static ENEMY_VEL: f32 = 0.5;
+pub(crate) static BULLET_VEL: f32 = 50.0;
+pub(crate) static ENEMY_SIZE: f32 = 0.3;
+pub(crate) static ENEMY_VEL: f32 = 0.5;
+pub(crate) static ENEMY_GUN_PERIOD: f32 = 1.0;
 
 fn main() {
+    let net_config = NetConfig::from_args();
+    let transport =
+        Transport::bind(net_config).expect("failed to bind UDP socket for rollback netcode");
+
     App::new()
         .add_plugins(DefaultPlugins.set(WindowPlugin {
             primary_window: Some(Window {
@@ -25,25 +44,58 @@ fn main() {
             ..default()
         }))
         .add_plugins(WorldInspectorPlugin::new())
+        .add_plugins(camera::CameraPlugin)
+        .insert_resource(Time::<Fixed>::from_hz(FIXED_HZ))
+        .insert_resource(net_config)
+        .insert_resource(transport)
+        .insert_resource(RollbackHistory::default())
+        .insert_resource(FrameInputs::default())
+        .insert_resource(SimFrame::default())
+        .insert_resource(SimRng::default())
+        .insert_resource(Bindings::default())
+        .insert_resource(ActionState::default())
+        .add_event::<CollisionBegin>()
+        .init_schedule(Simulate)
+        .add_systems(
+            Simulate,
+            (
+                bevy::ecs::event::event_update_system::<CollisionBegin>,
+                move_road,
+                move_player,
+                shoot,
+                move_bullet,
+                enemy::move_enemies,
+                enemy::enemy_shoot,
+                enemy::move_enemy_bullet,
+                collision::detect_collisions,
+                on_bullet_enemy_collision,
+                on_enemy_bullet_player_collision,
+            )
+                .chain(),
+        )
         .add_systems(Startup, setup)
-        .add_systems(Update, move_road)
-        .add_systems(Update, move_player)
-        .add_systems(Update, shoot)
-        .add_systems(Update, move_bullet)
-        .add_systems(Update, move_enemies)
+        .add_systems(FixedUpdate, (gather_input, rollback_driver).chain())
         .run();
 }
 
 #[derive(Component)]
 pub struct Player;
 
+/// Which side of the rollback a `Player` entity represents, so
+/// `move_player` knows whether to read `FrameInputs::local` or
+/// `FrameInputs::remote` for it.
+#[derive(Component, Clone, Copy, PartialEq, Eq)]
+pub enum PlayerSlot {
+    Local,
+    Remote,
+}
+
 #[derive(Component)]
 pub struct Road;
 
-fn move_road(time: Res<Time>, mut transform: Query<&mut Transform, With<Road>>) {
+fn move_road(mut transform: Query<&mut Transform, With<Road>>) {
     for mut t in &mut transform {
-        let secs = time.delta_seconds();
-        t.translation.z -= secs * ROAD_VEL;
+        t.translation.z -= net::FIXED_DT * ROAD_VEL;
 
         let div = t.translation.z % LEN_SEG;
         if t.translation.z.abs() > 1. && div < 0.01 {
@@ -52,68 +104,41 @@ fn move_road(time: Res<Time>, mut transform: Query<&mut Transform, With<Road>>)
     }
 }
 
-/// returns desired x,z velocity based on what keys are pressed
-fn decode_move(input: &ButtonInput<KeyCode>, elapsed: f32) -> (f32, f32) {
-    let mut x = 0.0;
-    let mut z = 0.0;
-    if input.pressed(KeyCode::ArrowLeft) {
-        x = -PLAYER_VEL;
-    }
-    if input.pressed(KeyCode::ArrowRight) {
-        x = PLAYER_VEL;
-    }
-    if input.pressed(KeyCode::ArrowUp) {
-        z = -PLAYER_VEL;
-    }
-    if input.pressed(KeyCode::ArrowDown) {
-        z = PLAYER_VEL;
-    }
-
-    (x * elapsed, z * elapsed)
+/// returns desired x,z displacement for this tick from a (possibly analog)
+/// -1..1 movement axis
+fn decode_move(input: PackedInput, elapsed: f32) -> (f32, f32) {
+    let axis = input.move_axis();
+    (axis.x * PLAYER_VEL * elapsed, axis.y * PLAYER_VEL * elapsed)
 }
 
 #[derive(Component, Default)]
 pub struct Gun {
     handles: Option<(Handle<Mesh>, Handle<StandardMaterial>)>,
-    last_fired: f32,
+    /// last `SimFrame` this gun fired on, rather than a wall-clock
+    /// timestamp, so cooldown checks replay identically during a rollback
+    last_fired: u32,
 }
 
 #[derive(Component)]
 pub struct Bullet;
 
-#[derive(Component)]
-pub struct Enemy;
-
 fn move_player(
-    time: Res<Time>,
-    mut transform: Query<&mut Transform, With<Player>>,
-    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut players: Query<(&mut Transform, &PlayerSlot), With<Player>>,
+    inputs: Res<FrameInputs>,
 ) {
     let x_width = 3.6;
-    let mut transform = transform.single_mut();
-    let secs = time.delta_seconds();
-    let tl = &mut transform.translation;
-    let (dx, dz) = decode_move(&keyboard_input, secs);
-    tl.z += dz;
-    tl.x += dx;
-    // keep player in bounds
-    tl.x = tl.x.clamp(-x_width / 2.0, x_width / 2.0);
-    tl.z = tl.z.clamp(0.0, 5.0);
-}
-
-fn move_enemies(
-    time: Res<Time>,
-    mut transforms: Query<&mut Transform, With<Enemy>>,
-    player_pos: Query<&Transform, With<Player>>,
-) {
-    for transform in &mut transforms {
-        let mut t = transform.translation;
-        t.z -= ENEMY_VEL * time.delta_seconds();
-        if let Ok(player_pos) = player_pos.get_single() {
-            t.z = t.z.min(player_pos.translation.z);
-        } else {
-            println!("no player????");
-        }
+    for (mut transform, slot) in &mut players {
+        let input = match slot {
+            PlayerSlot::Local => inputs.local,
+            PlayerSlot::Remote => inputs.remote,
+        };
+        let tl = &mut transform.translation;
+        let (dx, dz) = decode_move(input, net::FIXED_DT);
+        tl.z += dz;
+        tl.x += dx;
+        // keep player in bounds
+        tl.x = tl.x.clamp(-x_width / 2.0, x_width / 2.0);
+        tl.z = tl.z.clamp(0.0, 5.0);
     }
 }
 
@@ -121,25 +146,30 @@ fn shoot(
     mut commands: Commands,
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<StandardMaterial>>,
-    time: Res<Time>,
-    mut gun_pos: Query<(&GlobalTransform, &mut Gun)>,
+    frame: Res<SimFrame>,
+    inputs: Res<FrameInputs>,
+    mut gun_pos: Query<(&Transform, &mut Gun, &PlayerSlot)>,
 ) {
-    for (pos, mut gun) in &mut gun_pos {
-        let now = time.elapsed_seconds();
-
-        if now - gun.last_fired > PLAYER_GUN_PERIOD {
-            gun.last_fired = now
-        } else {
+    let period_frames = (PLAYER_GUN_PERIOD * FIXED_HZ as f32) as u32;
+    for (pos, mut gun, slot) in &mut gun_pos {
+        let fire = match slot {
+            PlayerSlot::Local => inputs.local.fire(),
+            PlayerSlot::Remote => inputs.remote.fire(),
+        };
+        if !fire || frame.0 - gun.last_fired <= period_frames {
             continue;
         }
+        gun.last_fired = frame.0;
         // no time for monad mental gymnastics, I have games to build
         let (mesh, material) = if gun.handles.is_some() {
             let (mesh, material) = gun.handles.clone().unwrap();
             (mesh, material)
         } else {
             let mesh = meshes.add(Sphere::new(0.05));
+            // pushed above 1.0 so it blows out the HDR bloom pass instead
+            // of just looking like a flat yellow dot
             let material = materials.add(StandardMaterial {
-                base_color: Color::YELLOW,
+                base_color: Color::rgb(6.0, 6.0, 0.4),
                 unlit: true,
                 ..default()
             });
@@ -149,20 +179,24 @@ fn shoot(
 
         commands
             .spawn(PbrBundle {
-                transform: Transform::default().with_translation(pos.translation()),
+                transform: Transform::default().with_translation(pos.translation),
                 mesh,
                 material,
                 ..default()
             })
-            .insert(Bullet);
+            .insert(Bullet)
+            .insert(Sweep::default())
+            .insert(CollisionBox {
+                half_extents: Vec3::splat(0.05),
+            })
+            .insert(CollisionLayer::BULLET)
+            .insert(CollisionMask::ENEMY);
     }
 }
 
 fn move_bullet(
     mut commands: Commands,
-    time: Res<Time>,
-    mut bullet_pos: Query<(&mut Transform, Entity), With<Bullet>>,
-    enemies: Query<(&GlobalTransform, Entity), With<Enemy>>,
+    mut bullet_pos: Query<(&mut Transform, &mut Sweep, Entity), With<Bullet>>,
 ) {
     // we have move_bullet and move_player, why not combine into Velocity component or smth?
     // I think it's not actually a great idea: they move in different ways and I don't want to
@@ -170,29 +204,69 @@ fn move_bullet(
     // eg: bullets need hit detection, player needs to respond to controls, road needs to loop back
     // so their similarities mostly end at x += v * delta_t, and this logic alone does not warrant
     // breaking out
+    //
+    // hit detection against enemies used to live here as a hand-rolled swept
+    // z-crossing check; that's now `collision::detect_collisions` plus
+    // `on_bullet_enemy_collision` reacting to `CollisionBegin`, with `Sweep`
+    // below expanding the bullet's AABB along its travel so it can't tunnel
+    // through a thin enemy between ticks
 
-    for (mut p, e) in &mut bullet_pos {
+    for (mut p, mut sweep, e) in &mut bullet_pos {
         if p.translation.z.abs() > 100. {
-            commands.entity(e).despawn()
+            commands.entity(e).despawn();
+            continue;
         }
-        let new_z = p.translation.z - time.delta_seconds() * BULLET_VEL;
-        let mut was_kil = false;
-        for (e_p, e_e) in &enemies {
-            if p.translation.z >= e_p.translation().z && new_z <= e_p.translation().z {
-                // crosses over
-                if (p.translation.x - e_p.translation().x).abs() <= ENEMY_SIZE / 2.0 {
-                    println!("GOTTEM");
-                    was_kil = true;
-                    commands.entity(e_e).despawn();
-                    break;
-                }
-            }
+        let displacement = Vec3::new(0.0, 0.0, -net::FIXED_DT * BULLET_VEL);
+        sweep.0 = displacement;
+        p.translation += displacement;
+    }
+}
+
+/// Reacts to `CollisionBegin` between a `Bullet` and an `Enemy`: despawns
+/// both. Geometry lives in `collision::detect_collisions`; this just knows
+/// what a bullet hitting an enemy *means*.
+fn on_bullet_enemy_collision(
+    mut commands: Commands,
+    mut events: EventReader<CollisionBegin>,
+    bullets: Query<(), With<Bullet>>,
+    enemies: Query<(), With<Enemy>>,
+) {
+    for ev in events.read() {
+        let pair = if bullets.contains(ev.a) && enemies.contains(ev.b) {
+            Some((ev.a, ev.b))
+        } else if bullets.contains(ev.b) && enemies.contains(ev.a) {
+            Some((ev.b, ev.a))
+        } else {
+            None
+        };
+        if let Some((bullet, enemy)) = pair {
+            println!("GOTTEM");
+            commands.entity(bullet).despawn();
+            commands.entity(enemy).despawn();
         }
-        if was_kil {
-            // collision consumes the bullet
-            commands.entity(e).despawn()
+    }
+}
+
+/// Reacts to `CollisionBegin` between an `EnemyBullet` and a `Player`.
+/// There's no player health system yet, so for now getting hit just
+/// consumes the bullet.
+fn on_enemy_bullet_player_collision(
+    mut commands: Commands,
+    mut events: EventReader<CollisionBegin>,
+    enemy_bullets: Query<(), With<EnemyBullet>>,
+    players: Query<(), With<Player>>,
+) {
+    for ev in events.read() {
+        let bullet = if enemy_bullets.contains(ev.a) && players.contains(ev.b) {
+            Some(ev.a)
+        } else if enemy_bullets.contains(ev.b) && players.contains(ev.a) {
+            Some(ev.b)
         } else {
-            p.translation.z = new_z;
+            None
+        };
+        if let Some(bullet) = bullet {
+            println!("ouch");
+            commands.entity(bullet).despawn();
         }
     }
 }
@@ -226,7 +300,7 @@ fn setup(
             }
         });
 
-    // player
+    // local player
     commands
         .spawn(PbrBundle {
             mesh: meshes.add(Cuboid::new(0.1, 0.1, 0.1)),
@@ -235,17 +309,33 @@ fn setup(
             ..default()
         })
         .insert(Player {})
-        .insert(Gun::default());
+        .insert(PlayerSlot::Local)
+        .insert(Gun::default())
+        .insert(CollisionBox {
+            half_extents: Vec3::splat(0.05),
+        })
+        .insert(CollisionLayer::PLAYER)
+        .insert(CollisionMask::ENEMY);
 
-    // enemies
+    // co-op buddy, driven by the remote peer's input over the rollback socket
     commands
         .spawn(PbrBundle {
-            mesh: meshes.add(Cuboid::new(ENEMY_SIZE, ENEMY_SIZE, ENEMY_SIZE)),
-            material: materials.add(Color::RED),
-            transform: Transform::from_xyz(0.0, ENEMY_SIZE / 2.0, -5.0),
+            mesh: meshes.add(Cuboid::new(0.1, 0.1, 0.1)),
+            material: materials.add(Color::rgb_u8(255, 144, 124)),
+            transform: Transform::from_xyz(1.0, 0.1 / 2.0, 0.0),
             ..default()
         })
-        .insert(Enemy {});
+        .insert(Player {})
+        .insert(PlayerSlot::Remote)
+        .insert(Gun::default())
+        .insert(CollisionBox {
+            half_extents: Vec3::splat(0.05),
+        })
+        .insert(CollisionLayer::PLAYER)
+        .insert(CollisionMask::ENEMY);
+
+    // enemies
+    enemy::spawn(&mut commands, &mut meshes, &mut materials);
 
     // light
     commands.spawn(PointLightBundle {
@@ -258,8 +348,8 @@ fn setup(
     });
 
     // camera
-    commands.spawn(Camera3dBundle {
-        transform: Transform::from_xyz(0.0, 2.0, 6.0).looking_at(Vec3::new(0., 0.5, 0.), Vec3::Y),
-        ..default()
-    });
+    camera::spawn_camera(
+        &mut commands,
+        Transform::from_xyz(0.0, 2.0, 6.0).looking_at(Vec3::new(0., 0.5, 0.), Vec3::Y),
+    );
 }
@@ -0,0 +1,579 @@
+//! Deterministic rollback netcode.
+//!
+//! The idea: every gameplay system is a pure function of `(prior state,
+//! per-frame input)`, so two peers can each predict the other's input,
+//! simulate ahead of the network, and rewind + resimulate when a remote
+//! input finally arrives and turns out to differ from the guess. None of
+//! this works if a system peeks at wall-clock time or raw device state, so
+//! `move_road`/`move_player`/`shoot`/`move_bullet`/`move_enemies` all run
+//! inside the `Simulate` schedule off `FIXED_DT` and `Res<FrameInputs>`
+//! instead of `Res<Time>` / `Res<ButtonInput<KeyCode>>`.
+
+use bevy::ecs::schedule::ScheduleLabel;
+use bevy::ecs::system::SystemState;
+use bevy::prelude::*;
+use bytemuck::{Pod, Zeroable};
+use rand::rngs::SmallRng;
+use rand::SeedableRng;
+use std::collections::HashMap;
+use std::net::{SocketAddr, UdpSocket};
+
+use crate::collision::{CollisionBox, CollisionLayer, CollisionMask, Sweep};
+use crate::enemy::{Enemy, EnemyBullet, EnemyState, PatrolBounds, PatrolTarget};
+use crate::input::ActionState;
+use crate::{Bullet, Gun, PlayerSlot, Road, ENEMY_SIZE};
+
+/// Ticks per second for the deterministic simulation. Must match on both
+/// peers or the rollback will slowly diverge.
+pub const FIXED_HZ: f64 = 60.0;
+/// Keep in sync with `FIXED_HZ` above (const fn float division isn't worth
+/// the ceremony here).
+pub const FIXED_DT: f32 = 1.0 / 60.0;
+/// How many past frames we keep full snapshots for, i.e. how far behind a
+/// remote input is allowed to arrive before we just have to eat the
+/// misprediction.
+const ROLLBACK_FRAMES: usize = 16;
+
+/// The deterministic gameplay schedule. Systems added here must only read
+/// `Res<FrameInputs>`/`Res<SimFrame>`/`SimRng` and component state so they
+/// can be safely replayed from a saved `WorldSnapshot`.
+#[derive(ScheduleLabel, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Simulate;
+
+/// Current simulated frame number, advanced once per `Simulate` run. Gun
+/// cooldowns etc. key off this instead of `Time::elapsed_seconds()` so
+/// replaying a frame twice produces the same result both times.
+#[derive(Resource, Default, Clone, Copy)]
+pub struct SimFrame(pub u32);
+
+/// Seed shared by both peers so `SimRng` starts identically everywhere;
+/// there's no connect-time handshake to exchange one over yet, so for now
+/// this is just a fixed constant.
+const SIM_RNG_SEED: u64 = 0xCAFE_F00D_BADE_C0DE;
+
+/// Deterministic PRNG for anything gameplay-random that runs inside
+/// `Simulate` (e.g. `enemy::PatrolBounds::random_point`). `rand::thread_rng()`
+/// is seeded from OS entropy per-process, so two peers calling it would
+/// pick different patrol targets and diverge the instant an enemy finished
+/// a leg - every peer must instead seed from the same value and only ever
+/// advance this resource from inside `Simulate`, so it's captured and
+/// rewound by `WorldSnapshot` exactly like any other sim state.
+#[derive(Resource, Clone)]
+pub struct SimRng(pub SmallRng);
+
+impl Default for SimRng {
+    fn default() -> Self {
+        Self(SmallRng::seed_from_u64(SIM_RNG_SEED))
+    }
+}
+
+/// One frame of input, packed small enough to fit in a single UDP datagram
+/// alongside the frame number it belongs to. `move_x`/`move_z` are
+/// `ActionState::move_axis` quantized to a signed byte each, so an analog
+/// stick's tilt survives the trip over the wire instead of being flattened
+/// to on/off like a raw key would be.
+#[repr(C)]
+#[derive(Clone, Copy, Default, PartialEq, Eq, Pod, Zeroable)]
+pub struct PackedInput {
+    pub move_x: i8,
+    pub move_z: i8,
+    fire: u8,
+}
+
+impl PackedInput {
+    pub fn from_action_state(state: &ActionState) -> Self {
+        Self {
+            move_x: (state.move_axis.x * i8::MAX as f32) as i8,
+            move_z: (state.move_axis.y * i8::MAX as f32) as i8,
+            fire: state.fire as u8,
+        }
+    }
+
+    pub fn move_axis(self) -> Vec2 {
+        Vec2::new(
+            self.move_x as f32 / i8::MAX as f32,
+            self.move_z as f32 / i8::MAX as f32,
+        )
+    }
+
+    pub fn fire(self) -> bool {
+        self.fire != 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn packed_input_round_trips_move_axis_and_fire() {
+        let state = ActionState {
+            move_axis: Vec2::new(-1.0, 0.5),
+            fire: true,
+        };
+        let packed = PackedInput::from_action_state(&state);
+
+        assert!(packed.fire());
+        let axis = packed.move_axis();
+        // i8 quantization loses a little precision, so compare loosely
+        assert!((axis.x - state.move_axis.x).abs() < 0.01);
+        assert!((axis.y - state.move_axis.y).abs() < 0.01);
+    }
+
+    #[test]
+    fn packed_input_handles_extremes_and_centered_stick() {
+        let at_rest = PackedInput::from_action_state(&ActionState::default());
+        assert_eq!(at_rest.move_axis(), Vec2::ZERO);
+        assert!(!at_rest.fire());
+
+        let maxed = PackedInput::from_action_state(&ActionState {
+            move_axis: Vec2::new(1.0, -1.0),
+            fire: false,
+        });
+        let axis = maxed.move_axis();
+        assert!(axis.x > 0.99);
+        assert!(axis.y < -0.99);
+    }
+}
+
+/// The resolved input for the current `Simulate` tick: what the local
+/// player pressed, and what the remote player pressed (or is predicted to
+/// have pressed).
+#[derive(Resource, Default, Clone, Copy)]
+pub struct FrameInputs {
+    pub local: PackedInput,
+    pub remote: PackedInput,
+}
+
+/// Where to send our input packets, parsed from `--peer`/`--port` CLI args
+/// in `main`.
+#[derive(Resource, Clone, Copy)]
+pub struct NetConfig {
+    pub local_port: u16,
+    pub peer_addr: SocketAddr,
+}
+
+impl NetConfig {
+    /// Parses `--port <u16>` and `--peer <ip:port>` from the process args,
+    /// falling back to a sane default so `cargo run` still works for one
+    /// player poking at themselves.
+    pub fn from_args() -> Self {
+        let args: Vec<String> = std::env::args().collect();
+        let mut local_port = 7000u16;
+        let mut peer_addr: SocketAddr = "127.0.0.1:7001".parse().unwrap();
+        let mut i = 1;
+        while i < args.len() {
+            match args[i].as_str() {
+                "--port" => {
+                    if let Some(v) = args.get(i + 1) {
+                        local_port = v.parse().unwrap_or(local_port);
+                    }
+                    i += 1;
+                }
+                "--peer" => {
+                    if let Some(v) = args.get(i + 1) {
+                        peer_addr = v.parse().unwrap_or(peer_addr);
+                    }
+                    i += 1;
+                }
+                _ => {}
+            }
+            i += 1;
+        }
+        Self {
+            local_port,
+            peer_addr,
+        }
+    }
+}
+
+/// Non-blocking UDP socket plus the inbox of remote inputs we've actually
+/// received, keyed by the frame number they were sent for.
+#[derive(Resource)]
+pub struct Transport {
+    socket: UdpSocket,
+    peer_addr: SocketAddr,
+    remote_inputs: HashMap<u32, PackedInput>,
+}
+
+impl Transport {
+    pub fn bind(config: NetConfig) -> std::io::Result<Self> {
+        let socket = UdpSocket::bind(("0.0.0.0", config.local_port))?;
+        socket.set_nonblocking(true)?;
+        Ok(Self {
+            socket,
+            peer_addr: config.peer_addr,
+            remote_inputs: HashMap::new(),
+        })
+    }
+
+    fn send_input(&self, frame: u32, input: PackedInput) {
+        let mut packet = [0u8; 7];
+        packet[0..4].copy_from_slice(&frame.to_le_bytes());
+        packet[4..7].copy_from_slice(bytemuck::bytes_of(&input));
+        // best-effort: a dropped input packet just means the peer predicts
+        // a repeat, which is exactly what the rollback is for
+        let _ = self.socket.send_to(&packet, self.peer_addr);
+    }
+
+    /// Drains whatever's arrived on the socket without blocking the frame.
+    fn poll(&mut self) {
+        let mut buf = [0u8; 7];
+        loop {
+            match self.socket.recv_from(&mut buf) {
+                Ok((7, _)) => {
+                    let frame = u32::from_le_bytes(buf[0..4].try_into().unwrap());
+                    let input = *bytemuck::from_bytes::<PackedInput>(&buf[4..7]);
+                    self.remote_inputs.insert(frame, input);
+                }
+                Ok(_) => continue, // short/garbled packet, drop it
+                Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => break,
+                Err(e) => {
+                    warn!("net: recv_from failed: {e}");
+                    break;
+                }
+            }
+        }
+    }
+
+    fn received(&self, frame: u32) -> Option<PackedInput> {
+        self.remote_inputs.get(&frame).copied()
+    }
+}
+
+/// Per-enemy state captured by `WorldSnapshot`, enough to fully respawn an
+/// `Enemy` entity from scratch - a despawn (e.g. `on_bullet_enemy_collision`
+/// killing it) can't be undone by writing into a live entity, since the
+/// entity itself is gone, so enemies get the same nuke-and-respawn
+/// treatment as `Bullet`/`EnemyBullet`.
+#[derive(Clone)]
+struct EnemySnapshot {
+    transform: Transform,
+    mesh: Handle<Mesh>,
+    material: Handle<StandardMaterial>,
+    bounds: PatrolBounds,
+    target: PatrolTarget,
+    state: EnemyState,
+    gun_last_fired: u32,
+}
+
+/// Everything `Simulate` reads or writes that isn't recoverable just from
+/// `Transform` alone.
+#[derive(Clone)]
+struct WorldSnapshot {
+    player_local: Transform,
+    player_remote: Transform,
+    gun_local_fired: u32,
+    gun_remote_fired: u32,
+    enemies: Vec<EnemySnapshot>,
+    bullets: Vec<(Transform, Handle<Mesh>, Handle<StandardMaterial>)>,
+    enemy_bullets: Vec<(Transform, Handle<Mesh>, Handle<StandardMaterial>)>,
+    road: Transform,
+    rng: SimRng,
+}
+
+/// Ring buffer of saved states and the inputs that produced each one, so a
+/// late remote input can be diffed against the prediction we used and, if
+/// it disagrees, the frame can be replayed with the corrected input.
+#[derive(Resource)]
+pub struct RollbackHistory {
+    frame: u32,
+    snapshots: Vec<Option<WorldSnapshot>>,
+    inputs: Vec<FrameInputs>,
+    /// true until we've confirmed the remote input for that slot really
+    /// arrived (as opposed to being a repeat-last-input guess)
+    predicted: Vec<bool>,
+}
+
+impl Default for RollbackHistory {
+    fn default() -> Self {
+        Self {
+            frame: 0,
+            snapshots: vec![None; ROLLBACK_FRAMES],
+            inputs: vec![FrameInputs::default(); ROLLBACK_FRAMES],
+            predicted: vec![true; ROLLBACK_FRAMES],
+        }
+    }
+}
+
+impl RollbackHistory {
+    fn idx(frame: u32) -> usize {
+        frame as usize % ROLLBACK_FRAMES
+    }
+}
+
+/// Drives the whole rollback loop once per `FixedUpdate` tick:
+/// 1. send this frame's local input, resolve the remote input (real or
+///    predicted repeat-last),
+/// 2. if an earlier prediction turned out wrong, restore the snapshot from
+///    just before it and resimulate every frame up to now with corrected
+///    inputs,
+/// 3. step `Simulate` once more for the current frame and snapshot it.
+pub fn rollback_driver(world: &mut World) {
+    // take Transport/RollbackHistory out of the world by value so `world`
+    // stays free for the restore/resimulate/snapshot calls below
+    let action_state = *world.resource::<ActionState>();
+    let mut transport = world.remove_resource::<Transport>().unwrap();
+    let mut history = world.remove_resource::<RollbackHistory>().unwrap();
+    transport.poll();
+
+    let frame = history.frame;
+    let local = PackedInput::from_action_state(&action_state);
+    transport.send_input(frame, local);
+
+    let resolved = match transport.received(frame) {
+        Some(actual) => FrameInputs {
+            local,
+            remote: actual,
+        },
+        None => {
+            let repeated = history.inputs[RollbackHistory::idx(frame.wrapping_sub(1))].remote;
+            FrameInputs {
+                local,
+                remote: repeated,
+            }
+        }
+    };
+
+    // look for the oldest frame still marked "predicted" whose real input
+    // has now shown up and turned out to differ from the guess we used
+    let mut replay_from: Option<u32> = None;
+    for back in 1..ROLLBACK_FRAMES as u32 {
+        if back > frame {
+            break;
+        }
+        let check_frame = frame - back;
+        let idx = RollbackHistory::idx(check_frame);
+        if !history.predicted[idx] {
+            continue;
+        }
+        if let Some(actual) = transport.received(check_frame) {
+            if actual != history.inputs[idx].remote {
+                replay_from = Some(check_frame);
+            }
+            history.predicted[idx] = false;
+        }
+    }
+
+    if let Some(bad_frame) = replay_from {
+        // restore the last known-good state and march forward, re-running
+        // Simulate with the now-corrected input history
+        let restore_idx = RollbackHistory::idx(bad_frame.wrapping_sub(1));
+        if let Some(snap) = history.snapshots[restore_idx].clone() {
+            restore_snapshot(world, &snap);
+        }
+        for replay_frame in bad_frame..frame {
+            let idx = RollbackHistory::idx(replay_frame);
+            if let Some(actual) = transport.received(replay_frame) {
+                history.inputs[idx].remote = actual;
+            }
+            let inputs = history.inputs[idx];
+            run_simulate_step(world, replay_frame, inputs);
+            let snap = take_snapshot(world);
+            history.snapshots[idx] = Some(snap);
+        }
+    }
+
+    let idx = RollbackHistory::idx(frame);
+    history.inputs[idx] = resolved;
+    history.predicted[idx] = transport.received(frame).is_none();
+
+    run_simulate_step(world, frame, resolved);
+    let snap = take_snapshot(world);
+    let idx = RollbackHistory::idx(frame);
+    history.snapshots[idx] = Some(snap);
+    history.frame = frame.wrapping_add(1);
+
+    // write resources back since they were taken out by value above
+    world.insert_resource(transport);
+    world.insert_resource(history);
+}
+
+fn run_simulate_step(world: &mut World, frame: u32, inputs: FrameInputs) {
+    world.insert_resource(SimFrame(frame));
+    world.insert_resource(inputs);
+    world.run_schedule(Simulate);
+}
+
+fn take_snapshot(world: &mut World) -> WorldSnapshot {
+    let mut state: SystemState<(
+        Query<(&Transform, &PlayerSlot, &Gun)>,
+        Query<
+            (
+                &Transform,
+                &Handle<Mesh>,
+                &Handle<StandardMaterial>,
+                &PatrolBounds,
+                &PatrolTarget,
+                &EnemyState,
+                &Gun,
+            ),
+            With<Enemy>,
+        >,
+        Query<(&Transform, &Handle<Mesh>, &Handle<StandardMaterial>), With<Bullet>>,
+        Query<(&Transform, &Handle<Mesh>, &Handle<StandardMaterial>), With<EnemyBullet>>,
+        Query<&Transform, With<Road>>,
+        Res<SimRng>,
+    )> = SystemState::new(world);
+    let (players, enemies, bullets, enemy_bullets, road, rng) = state.get(world);
+
+    let mut player_local = Transform::IDENTITY;
+    let mut player_remote = Transform::IDENTITY;
+    let mut gun_local_fired = 0;
+    let mut gun_remote_fired = 0;
+    for (t, slot, gun) in &players {
+        match slot {
+            PlayerSlot::Local => {
+                player_local = *t;
+                gun_local_fired = gun.last_fired;
+            }
+            PlayerSlot::Remote => {
+                player_remote = *t;
+                gun_remote_fired = gun.last_fired;
+            }
+        }
+    }
+
+    WorldSnapshot {
+        player_local,
+        player_remote,
+        gun_local_fired,
+        gun_remote_fired,
+        enemies: enemies
+            .iter()
+            .map(|(t, mesh, mat, bounds, target, state, gun)| EnemySnapshot {
+                transform: *t,
+                mesh: mesh.clone(),
+                material: mat.clone(),
+                bounds: bounds.clone(),
+                target: *target,
+                state: *state,
+                gun_last_fired: gun.last_fired,
+            })
+            .collect(),
+        bullets: bullets
+            .iter()
+            .map(|(t, mesh, mat)| (*t, mesh.clone(), mat.clone()))
+            .collect(),
+        enemy_bullets: enemy_bullets
+            .iter()
+            .map(|(t, mesh, mat)| (*t, mesh.clone(), mat.clone()))
+            .collect(),
+        road: road.iter().copied().collect::<Vec<_>>().remove(0),
+        rng: rng.clone(),
+    }
+}
+
+fn restore_snapshot(world: &mut World, snap: &WorldSnapshot) {
+    let mut state: SystemState<(
+        Query<(&mut Transform, &PlayerSlot, &mut Gun)>,
+        Query<Entity, With<Enemy>>,
+        Query<Entity, With<Bullet>>,
+        Query<Entity, With<EnemyBullet>>,
+        Query<&mut Transform, (With<Road>, Without<Enemy>, Without<PlayerSlot>)>,
+        ResMut<SimRng>,
+    )> = SystemState::new(world);
+    let (mut players, enemies, bullets, enemy_bullets, mut road, mut rng) = state.get_mut(world);
+    *rng = snap.rng.clone();
+
+    for (mut t, slot, mut gun) in &mut players {
+        match slot {
+            PlayerSlot::Local => {
+                *t = snap.player_local;
+                gun.last_fired = snap.gun_local_fired;
+            }
+            PlayerSlot::Remote => {
+                *t = snap.player_remote;
+                gun.last_fired = snap.gun_remote_fired;
+            }
+        }
+    }
+
+    if let Some(mut t) = road.iter_mut().next() {
+        *t = snap.road;
+    }
+
+    // a despawned Enemy (e.g. killed by on_bullet_enemy_collision) can't be
+    // un-despawned by writing into a live entity - that entity is just gone
+    // - so enemies get the same nuke-and-respawn treatment as bullets below
+    // instead of zip()-ing against whatever currently happens to exist.
+    // collect every entity list before the first despawn: the queries borrow
+    // `world` until their last use
+    let enemy_entities: Vec<Entity> = enemies.iter().collect();
+    let bullet_entities: Vec<Entity> = bullets.iter().collect();
+    let enemy_bullet_entities: Vec<Entity> = enemy_bullets.iter().collect();
+    for e in enemy_entities {
+        world.despawn(e);
+    }
+    for saved in &snap.enemies {
+        world.spawn((
+            PbrBundle {
+                transform: saved.transform,
+                mesh: saved.mesh.clone(),
+                material: saved.material.clone(),
+                ..default()
+            },
+            Enemy,
+            Gun {
+                last_fired: saved.gun_last_fired,
+                ..default()
+            },
+            saved.bounds.clone(),
+            saved.target,
+            saved.state,
+            CollisionBox {
+                half_extents: Vec3::splat(ENEMY_SIZE / 2.0),
+            },
+            CollisionLayer::ENEMY,
+            CollisionMask::PLAYER,
+        ));
+    }
+
+    // bullets are spawned/despawned during Simulate, so rather than try to
+    // line up entity ids across a rewind we just nuke and respawn them from
+    // the snapshot instead of diffing the set. Re-insert the same collision
+    // bundle shoot()/enemy_shoot() attach on first spawn, or a rewound
+    // bullet comes back with no Sweep/CollisionBox/layer/mask and can never
+    // hit anything again.
+    for e in bullet_entities {
+        world.despawn(e);
+    }
+    for (t, mesh, mat) in &snap.bullets {
+        world.spawn((
+            PbrBundle {
+                transform: *t,
+                mesh: mesh.clone(),
+                material: mat.clone(),
+                ..default()
+            },
+            Bullet,
+            Sweep::default(),
+            CollisionBox {
+                half_extents: Vec3::splat(0.05),
+            },
+            CollisionLayer::BULLET,
+            CollisionMask::ENEMY,
+        ));
+    }
+
+    for e in enemy_bullet_entities {
+        world.despawn(e);
+    }
+    for (t, mesh, mat) in &snap.enemy_bullets {
+        world.spawn((
+            PbrBundle {
+                transform: *t,
+                mesh: mesh.clone(),
+                material: mat.clone(),
+                ..default()
+            },
+            EnemyBullet,
+            Sweep::default(),
+            CollisionBox {
+                half_extents: Vec3::splat(0.05),
+            },
+            CollisionLayer::BULLET,
+            CollisionMask::PLAYER,
+        ));
+    }
+}